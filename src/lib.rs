@@ -0,0 +1,688 @@
+use std::ops::Range;
+
+/// The base a numeric range renders its values in.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Radix {
+    Decimal,
+    Hex,
+}
+
+/// A single numeric range within a bracket group, e.g. the `1-100/10` in
+/// `host[1-100/10]`, or a hex range like `0x0-0xff`. Carries enough state to
+/// walk the range in either direction at a given step: `10-1` counts down
+/// one at a time, `20-2/2` counts down by twos.
+#[derive(Debug, PartialEq, Clone)]
+struct NumRange {
+    start: u64,
+    end: u64,
+    step: u64,
+    descending: bool,
+    /// zero-padded field width taken from the spec (e.g. `2` for `01-10`),
+    /// or `0` if the spec didn't ask for padding
+    width: usize,
+    radix: Radix,
+}
+
+impl NumRange {
+    /// number of values this range produces, or `None` if that count
+    /// doesn't fit in a `u64` (e.g. a full `0-18446744073709551615` span)
+    fn len(&self) -> Option<u64> {
+        let span = if self.descending {
+            self.start.checked_sub(self.end)
+        } else {
+            self.end.checked_sub(self.start)
+        }?;
+        (span / self.step).checked_add(1)
+    }
+
+    /// the `idx`-th value (0-based) walking from `start` towards `end`,
+    /// zero-padded to this range's field width and rendered in its radix
+    fn nth(&self, idx: u64) -> String {
+        let value = if self.descending {
+            self.start - idx * self.step
+        } else {
+            self.start + idx * self.step
+        };
+        match self.radix {
+            Radix::Decimal => format!("{:01$}", value, self.width),
+            Radix::Hex => format!("0x{:01$x}", value, self.width),
+        }
+    }
+}
+
+/// A single-codepoint alphabetic range within a bracket group, e.g. `a-f` in
+/// `host[a-f]`. Both bounds are the same ASCII case.
+#[derive(Debug, PartialEq, Clone)]
+struct CharRange {
+    start: char,
+    end: char,
+    descending: bool,
+}
+
+impl CharRange {
+    /// number of values this range produces
+    fn len(&self) -> u64 {
+        let span = if self.descending {
+            self.start as u64 - self.end as u64
+        } else {
+            self.end as u64 - self.start as u64
+        };
+        span + 1
+    }
+
+    /// the `idx`-th character (0-based) walking from `start` towards `end`
+    fn nth(&self, idx: u64) -> String {
+        let value = if self.descending {
+            self.start as u32 - idx as u32
+        } else {
+            self.start as u32 + idx as u32
+        };
+        char::from_u32(value)
+            .expect("codepoint stays within the a-z/A-Z range it was validated against")
+            .to_string()
+    }
+}
+
+/// One element of a range-set: a numeric (decimal or hex) range, or an
+/// alphabetic range.
+#[derive(Debug, PartialEq, Clone)]
+enum RangeElem {
+    Numeric(NumRange),
+    Alpha(CharRange),
+}
+
+impl RangeElem {
+    /// number of values this element produces, or `None` on overflow (see
+    /// `NumRange::len`)
+    fn len(&self) -> Option<u64> {
+        match self {
+            RangeElem::Numeric(r) => r.len(),
+            RangeElem::Alpha(r) => Some(r.len()),
+        }
+    }
+
+    fn nth(&self, idx: u64) -> String {
+        match self {
+            RangeElem::Numeric(r) => r.nth(idx),
+            RangeElem::Alpha(r) => r.nth(idx),
+        }
+    }
+}
+
+/// A set of (possibly disjoint) ranges parsed from a single bracket group,
+/// e.g. `[10-100,500]` is a range-set covering `10..=100` and `500..=500`.
+/// Ranges are addressed by a single flat index so a range-set behaves like
+/// one axis of the cartesian product, regardless of how many sub-ranges
+/// it's made of.
+#[derive(Debug, PartialEq, Clone)]
+struct RangeSet(Vec<RangeElem>);
+
+impl RangeSet {
+    /// total number of values covered by this range-set, or `None` if
+    /// any sub-range (or their sum) overflows a `u64`
+    fn len(&self) -> Option<u64> {
+        self.0
+            .iter()
+            .try_fold(0u64, |acc, r| acc.checked_add(r.len()?))
+    }
+
+    /// the value at flat index `idx` across all sub-ranges, in order,
+    /// rendered with each sub-range's own field width
+    fn nth(&self, mut idx: u64) -> String {
+        for r in &self.0 {
+            let span = r.len().expect("range-set length already validated at parse time");
+            if idx < span {
+                return r.nth(idx);
+            }
+            idx -= span;
+        }
+        unreachable!("flat index out of bounds for range-set")
+    }
+}
+
+/// A single element of a hostspec: either a literal chunk of text, or a
+/// numeric range-set that expands into one axis of a cartesian product.
+#[derive(Debug, PartialEq, Clone)]
+enum Segment {
+    Literal(String),
+    Range(RangeSet),
+}
+
+/// A parsed hostspec, e.g. `rack[1-2]-node[1-3]`, represented as an ordered
+/// sequence of segments plus odometer state used to walk the cartesian
+/// product of all range segments. Iterates the expanded hostnames one at a
+/// time.
+#[derive(Debug, PartialEq)]
+pub struct HostSpec {
+    segments: Vec<Segment>,
+    /// flat index into each range segment, in the same order the range
+    /// segments appear in `segments`
+    cursor: Vec<u64>,
+    exhausted: bool,
+}
+
+impl HostSpec {
+    fn new(segments: Vec<Segment>) -> Self {
+        let exhausted = segments.iter().any(|s| {
+            matches!(s, Segment::Range(r) if r.len().expect("range-set length already validated at parse time") == 0)
+        });
+        let cursor = segments
+            .iter()
+            .filter(|s| matches!(s, Segment::Range(_)))
+            .map(|_| 0)
+            .collect();
+        HostSpec {
+            segments,
+            cursor,
+            exhausted,
+        }
+    }
+
+    fn from_single(host: &str) -> Result<Self, ParseError> {
+        let prefix = host.trim_end_matches(|c: char| c.is_ascii_digit());
+        // unwrap here, but stripping what we know is the first part of the string, from the string, should never fail
+        let numeric_part = host.strip_prefix(prefix).unwrap();
+        let host_number = numeric_part.parse::<u64>().map_err(|_| ParseError::BadNumbers {
+            span: span_of(host, numeric_part),
+        })?;
+        Ok(HostSpec::new(vec![
+            Segment::Literal(prefix.to_string()),
+            Segment::Range(RangeSet(vec![RangeElem::Numeric(NumRange {
+                start: host_number,
+                end: host_number,
+                step: 1,
+                descending: false,
+                width: 0,
+                radix: Radix::Decimal,
+            })])),
+        ]))
+    }
+
+    /// Render the segments at their current cursor positions into a single
+    /// host string.
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let mut cursor_idx = 0;
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => out.push_str(s),
+                Segment::Range(r) => {
+                    out.push_str(&r.nth(self.cursor[cursor_idx]));
+                    cursor_idx += 1;
+                }
+            }
+        }
+        out
+    }
+
+    /// Advance the odometer by one step: increment the last range segment's
+    /// flat index, carrying into earlier range segments when one overflows
+    /// its range-set. Returns `false` once the first segment itself
+    /// overflows, meaning the spec is exhausted.
+    fn advance(&mut self) -> bool {
+        let ranges: Vec<&RangeSet> = self
+            .segments
+            .iter()
+            .filter_map(|s| match s {
+                Segment::Range(r) => Some(r),
+                Segment::Literal(_) => None,
+            })
+            .collect();
+
+        for i in (0..self.cursor.len()).rev() {
+            self.cursor[i] += 1;
+            if self.cursor[i] < ranges[i].len().expect("range-set length already validated at parse time") {
+                return true;
+            }
+            self.cursor[i] = 0;
+        }
+        false
+    }
+}
+
+impl Iterator for HostSpec {
+    type Item = String;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.exhausted {
+            return None;
+        }
+        let rendered = self.render();
+        if !self.advance() {
+            self.exhausted = true;
+        }
+        Some(rendered)
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ParseError {
+    #[error("the expression contained a spec with unknown extra characters (e.g after the closing ']' character)")]
+    ExtraStuff { span: Range<usize> },
+    #[error("the expression contained a spec with numbers that couldn't be understood, or no numbers at all")]
+    BadNumbers { span: Range<usize> },
+    #[error(
+        "the expression contained a spec that looked like a range[numbers], but was badly formed"
+    )]
+    NoRange { span: Range<usize> },
+}
+
+impl ParseError {
+    /// the byte span within the original arg that the error points at
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            ParseError::ExtraStuff { span }
+            | ParseError::BadNumbers { span }
+            | ParseError::NoRange { span } => span.clone(),
+        }
+    }
+}
+
+/// The byte span `sub` occupies within `root`, given `sub` is a substring of
+/// `root` produced by slicing (not reallocating) - used so every parsing
+/// function can report errors against the original arg without threading an
+/// offset through every call.
+fn span_of(root: &str, sub: &str) -> Range<usize> {
+    let start = sub.as_ptr() as usize - root.as_ptr() as usize;
+    start..start + sub.len()
+}
+
+/// The input remaining to be parsed, and the output produced so far - the
+/// parser-combinator convention used throughout this module.
+type PResult<'a, O> = Result<(&'a str, O), ParseError>;
+
+/// Parse one `start-end` or `start-end/step` element of a range-set, e.g.
+/// `1-100/10` (every 10th value ascending), `20-2/2` (every 2nd value
+/// descending), `a-f` (an alphabetic range) or `0x0-0xff` (a hex range). A
+/// bare `start` with no `-` is a range of one value. `root` is the original
+/// arg, used only to compute error spans.
+fn parse_range_element(root: &str, range_str: &str) -> Result<RangeElem, ParseError> {
+    let (bounds, step, step_str) = match range_str.split_once('/') {
+        Some((bounds, step_str)) => {
+            let step: u64 = step_str.parse().map_err(|_| ParseError::NoRange {
+                span: span_of(root, step_str),
+            })?;
+            if step == 0 {
+                return Err(ParseError::NoRange {
+                    span: span_of(root, step_str),
+                });
+            }
+            (bounds, step, Some(step_str))
+        }
+        None => (range_str, 1, None),
+    };
+
+    let (start_str, end_str) = bounds.split_once('-').unwrap_or((bounds, bounds));
+
+    if let (Some(start_c), Some(end_c)) = (single_alpha(start_str), single_alpha(end_str)) {
+        if start_c.is_ascii_lowercase() != end_c.is_ascii_lowercase() {
+            return Err(ParseError::BadNumbers {
+                span: span_of(root, bounds),
+            });
+        }
+        if let Some(step_str) = step_str {
+            return Err(ParseError::NoRange {
+                span: span_of(root, step_str),
+            });
+        }
+        return Ok(RangeElem::Alpha(CharRange {
+            start: start_c,
+            end: end_c,
+            descending: start_c > end_c,
+        }));
+    }
+
+    if is_hex_literal(start_str) != is_hex_literal(end_str) {
+        return Err(ParseError::BadNumbers {
+            span: span_of(root, bounds),
+        });
+    }
+
+    // a range whose value count doesn't fit in a `u64` (e.g. the full
+    // `0-18446744073709551615` span) is rejected here rather than left to
+    // panic later when something actually asks for its length
+    let checked_numeric = |range: NumRange| -> Result<RangeElem, ParseError> {
+        range.len().ok_or_else(|| ParseError::NoRange {
+            span: span_of(root, bounds),
+        })?;
+        Ok(RangeElem::Numeric(range))
+    };
+
+    if is_hex_literal(start_str) && is_hex_literal(end_str) {
+        let width = field_width(hex_digits(start_str)).max(field_width(hex_digits(end_str)));
+        let start = parse_hex(start_str).ok_or_else(|| ParseError::BadNumbers {
+            span: span_of(root, start_str),
+        })?;
+        let end = parse_hex(end_str).ok_or_else(|| ParseError::BadNumbers {
+            span: span_of(root, end_str),
+        })?;
+        return checked_numeric(NumRange {
+            start,
+            end,
+            step,
+            descending: start > end,
+            width,
+            radix: Radix::Hex,
+        });
+    }
+
+    let width = field_width(start_str).max(field_width(end_str));
+    let start: u64 = start_str.parse().map_err(|_| ParseError::BadNumbers {
+        span: span_of(root, start_str),
+    })?;
+    let end: u64 = end_str.parse().map_err(|_| ParseError::BadNumbers {
+        span: span_of(root, end_str),
+    })?;
+    checked_numeric(NumRange {
+        start,
+        end,
+        step,
+        descending: start > end,
+        width,
+        radix: Radix::Decimal,
+    })
+}
+
+/// `Some(c)` if `s` is exactly one ASCII letter.
+fn single_alpha(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_alphabetic() => Some(c),
+        _ => None,
+    }
+}
+
+/// Whether `s` looks like a `0x`/`0X`-prefixed hex literal.
+fn is_hex_literal(s: &str) -> bool {
+    s.starts_with("0x") || s.starts_with("0X")
+}
+
+/// The hex digits of a `0x`/`0X`-prefixed literal, with the prefix stripped.
+fn hex_digits(s: &str) -> &str {
+    s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s)
+}
+
+/// Parse a `0x`/`0X`-prefixed hex literal into its value.
+fn parse_hex(s: &str) -> Option<u64> {
+    u64::from_str_radix(hex_digits(s), 16).ok()
+}
+
+/// The zero-padded field width implied by a bound's literal text, e.g. `2`
+/// for `"01"`, `3` for `"008"`, but `0` for `"1"` (no leading zero, no padding).
+fn field_width(bound: &str) -> usize {
+    if bound.len() > 1 && bound.starts_with('0') {
+        bound.len()
+    } else {
+        0
+    }
+}
+
+/// Parse a single `[a-b,c,...]` bracket group into one range-set segment.
+fn parse_range_group<'a>(root: &str, input: &'a str) -> PResult<'a, Segment> {
+    let rest = input.strip_prefix('[').ok_or_else(|| ParseError::NoRange {
+        span: span_of(root, input),
+    })?;
+    let (range, rest) = rest.split_once(']').ok_or_else(|| ParseError::NoRange {
+        span: span_of(root, input),
+    })?;
+    let ranges = range
+        .split(',')
+        .map(|r| parse_range_element(root, r))
+        .collect::<Result<Vec<_>, _>>()?;
+    let range_set = RangeSet(ranges);
+    // combining multiple sub-ranges can itself overflow even when each one
+    // individually fits in a `u64`
+    range_set.len().ok_or_else(|| ParseError::NoRange {
+        span: span_of(root, range),
+    })?;
+    Ok((rest, Segment::Range(range_set)))
+}
+
+/// Parse the literal run of characters up to (but not including) the next
+/// `[`, or to the end of input if there is no bracket group. A stray `]`
+/// with no matching `[` in this run is unknown extra characters.
+fn parse_literal<'a>(root: &str, input: &'a str) -> PResult<'a, Segment> {
+    let end = input.find('[').unwrap_or(input.len());
+    if let Some(stray) = input[..end].find(']') {
+        return Err(ParseError::ExtraStuff {
+            span: span_of(root, &input[stray..stray + 1]),
+        });
+    }
+    let (literal, rest) = input.split_at(end);
+    Ok((rest, Segment::Literal(literal.to_string())))
+}
+
+/// Tokenize a single hostspec argument into an ordered sequence of literal
+/// and range segments, e.g. `rack[1-2]-node[1-3]` becomes
+/// `[Literal("rack"), Range(1..=2), Literal("-node"), Range(1..=3)]`.
+fn tokenize(root: &str, mut input: &str) -> Result<Vec<Segment>, ParseError> {
+    let mut segments = Vec::new();
+    loop {
+        let (rest, literal) = parse_literal(root, input)?;
+        if !matches!(&literal, Segment::Literal(s) if s.is_empty()) {
+            segments.push(literal);
+        }
+        input = rest;
+        if input.is_empty() {
+            break;
+        }
+        // `parse_literal` always stops right at a `[` (or consumes to the
+        // end of input), so `input` is guaranteed to start with `[` here.
+        let (rest, range) = parse_range_group(root, input)?;
+        segments.push(range);
+        input = rest;
+    }
+    Ok(segments)
+}
+
+fn transform_single_hostspec(item: impl AsRef<str>) -> Result<HostSpec, ParseError> {
+    let raw: &str = item.as_ref();
+    if raw.contains('[') {
+        let segments = tokenize(raw, raw)?;
+        Ok(HostSpec::new(segments))
+    } else {
+        HostSpec::from_single(raw)
+    }
+}
+
+/// Parse a single hostspec expression (e.g. `rack[1-2]-node[1-3]`) into its
+/// `HostSpec`, ready to iterate for the expanded hostnames.
+pub fn parse_hostspec(input: &str) -> Result<HostSpec, ParseError> {
+    transform_single_hostspec(input)
+}
+
+/// Lazily expand a sequence of hostspec expressions into their hostnames,
+/// without the process-exiting error handling the CLI uses. Errors for one
+/// item don't stop later items from being yielded.
+pub fn expand_all<I, S>(items: I) -> impl Iterator<Item = Result<String, ParseError>>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    items.into_iter().flat_map(|item| {
+        let expanded: Box<dyn Iterator<Item = Result<String, ParseError>>> =
+            match transform_single_hostspec(item.as_ref()) {
+                Ok(spec) => Box::new(spec.map(Ok)),
+                Err(e) => Box::new(std::iter::once(Err(e))),
+            };
+        expanded
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        parse_hostspec, transform_single_hostspec, HostSpec, NumRange, Radix, RangeElem, RangeSet,
+        Segment,
+    };
+
+    #[test]
+    fn test_basics() -> anyhow::Result<()> {
+        assert_eq!(
+            transform_single_hostspec("host[1234]")?,
+            HostSpec::new(vec![
+                Segment::Literal("host".into()),
+                Segment::Range(RangeSet(vec![RangeElem::Numeric(NumRange {
+                    start: 1234,
+                    end: 1234,
+                    step: 1,
+                    descending: false,
+                    width: 0,
+                    radix: Radix::Decimal,
+                })])),
+            ])
+        );
+        assert_eq!(
+            transform_single_hostspec("host[10-100,500]")?
+                .collect::<Vec<_>>()
+                .len(),
+            92
+        );
+        assert_eq!(
+            transform_single_hostspec("xxx[1,2]")?.collect::<Vec<_>>(),
+            vec!["xxx1", "xxx2"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_bracket_groups() -> anyhow::Result<()> {
+        let hosts: Vec<String> = transform_single_hostspec("rack[1-2]-node[1-3]")?.collect();
+        assert_eq!(
+            hosts,
+            vec![
+                "rack1-node1",
+                "rack1-node2",
+                "rack1-node3",
+                "rack2-node1",
+                "rack2-node2",
+                "rack2-node3",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stepped_and_descending_ranges() -> anyhow::Result<()> {
+        let stepped: Vec<String> = transform_single_hostspec("host[1-100/10]")?.collect();
+        assert_eq!(
+            stepped,
+            vec!["host1", "host11", "host21", "host31", "host41", "host51", "host61", "host71",
+                "host81", "host91"]
+        );
+
+        let descending: Vec<String> = transform_single_hostspec("host[10-1]")?.collect();
+        assert_eq!(
+            descending,
+            vec![
+                "host10", "host9", "host8", "host7", "host6", "host5", "host4", "host3", "host2",
+                "host1",
+            ]
+        );
+
+        let stepped_descending: Vec<String> = transform_single_hostspec("host[20-2/2]")?.collect();
+        assert_eq!(
+            stepped_descending,
+            vec![
+                "host20", "host18", "host16", "host14", "host12", "host10", "host8", "host6",
+                "host4", "host2",
+            ]
+        );
+
+        let zero_step = transform_single_hostspec("host[1-100/0]").unwrap_err();
+        assert!(matches!(zero_step, crate::ParseError::NoRange { .. }));
+        assert_eq!(&"host[1-100/0]"[zero_step.span()], "0");
+
+        let bad_step = transform_single_hostspec("host[1-100/x]").unwrap_err();
+        assert!(matches!(bad_step, crate::ParseError::NoRange { .. }));
+        assert_eq!(&"host[1-100/x]"[bad_step.span()], "x");
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_padded_width() -> anyhow::Result<()> {
+        let padded: Vec<String> = transform_single_hostspec("host[01-10]")?.collect();
+        assert_eq!(
+            padded,
+            vec![
+                "host01", "host02", "host03", "host04", "host05", "host06", "host07", "host08",
+                "host09", "host10",
+            ]
+        );
+
+        let wider: Vec<String> = transform_single_hostspec("node[008-012]")?.collect();
+        assert_eq!(
+            wider,
+            vec!["node008", "node009", "node010", "node011", "node012"]
+        );
+
+        let unpadded: Vec<String> = transform_single_hostspec("host[1-10]")?.collect();
+        assert_eq!(unpadded[0], "host1");
+        assert_eq!(unpadded[9], "host10");
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_spans_point_at_the_offending_text() {
+        let bad_char = transform_single_hostspec("host[1-x0]").unwrap_err();
+        assert!(matches!(bad_char, crate::ParseError::BadNumbers { .. }));
+        assert_eq!(&"host[1-x0]"[bad_char.span()], "x0");
+
+        let stray_bracket = transform_single_hostspec("host[1-10]]").unwrap_err();
+        assert!(matches!(
+            stray_bracket,
+            crate::ParseError::ExtraStuff { .. }
+        ));
+        assert_eq!(&"host[1-10]]"[stray_bracket.span()], "]");
+    }
+
+    #[test]
+    fn test_alpha_ranges() -> anyhow::Result<()> {
+        let hosts: Vec<String> = transform_single_hostspec("host[a-f]")?.collect();
+        assert_eq!(
+            hosts,
+            vec!["hosta", "hostb", "hostc", "hostd", "hoste", "hostf"]
+        );
+
+        let descending: Vec<String> = transform_single_hostspec("host[d-a]")?.collect();
+        assert_eq!(descending, vec!["hostd", "hostc", "hostb", "hosta"]);
+
+        let mismatched_case = transform_single_hostspec("host[a-F]").unwrap_err();
+        assert!(matches!(mismatched_case, crate::ParseError::BadNumbers { .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn test_hex_ranges() -> anyhow::Result<()> {
+        let hosts: Vec<String> = transform_single_hostspec("node[0x0-0xf]")?.collect();
+        assert_eq!(
+            hosts,
+            vec![
+                "node0x0", "node0x1", "node0x2", "node0x3", "node0x4", "node0x5", "node0x6",
+                "node0x7", "node0x8", "node0x9", "node0xa", "node0xb", "node0xc", "node0xd",
+                "node0xe", "node0xf",
+            ]
+        );
+
+        let padded: Vec<String> = transform_single_hostspec("node[0x00-0x0a]")?.collect();
+        assert_eq!(padded[0], "node0x00");
+        assert_eq!(padded[10], "node0x0a");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_hostspec_public_api() -> anyhow::Result<()> {
+        let spec = parse_hostspec("host[1-3]")?;
+        let hosts: Vec<String> = spec.collect();
+        assert_eq!(hosts, vec!["host1", "host2", "host3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_all_streams_across_items_and_errors() {
+        let results: Vec<_> = crate::expand_all(["host[1-2]", "node[x-1]"]).collect();
+        assert_eq!(results[0].as_deref(), Ok("host1"));
+        assert_eq!(results[1].as_deref(), Ok("host2"));
+        assert!(results[2].is_err());
+    }
+}